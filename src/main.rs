@@ -2,9 +2,11 @@ use std::collections::HashSet;
 use std::convert::TryInto;
 use std::env;
 use std::fs::File;
-use std::io::{Read, Seek, SeekFrom};
+use std::io::{Cursor, Read, Seek, SeekFrom};
 use std::str::FromStr;
 
+use memmap2::Mmap;
+
 fn print_usage() -> Result<(), String> {
     eprint!(
         "\
@@ -13,6 +15,7 @@ MIT licensed.
 
 Usage:
     dream-sparer path/to/rifx/file
+    dream-sparer -                 (reads the file from stdin instead)
 
 With no other arguments passed, dream-sparer will just print a list of chunks
 found in the file.
@@ -28,14 +31,51 @@ Optional arguments:
   --dump-all=TYPE   When encountering a chunk of type TYPE, dump it to a file.
                     The files will be named the same way as for --dump.
                     You can specify multiple indices by repeating the argument.
+  --container=TYPE  Treat chunks of type TYPE as containers: read a 4-byte
+                    list kind from the start of their payload, then recurse
+                    into the chunks nested inside the remainder of the
+                    payload, printing them indented under their parent.
+                    Defaults to 'LIST', 'RIFX' and 'XFIR' if not specified.
+                    You can specify multiple types by repeating the argument.
   --translate-sndH  Not a generic RIFX option: specific to Macromedia Director.
-                    Tries to decode sound clip headers ('sndH') into format
-                    arguments understood by FFMPEG. One file is created for each
-                    chunk, like --dump-all.
+                    Tries to decode sound clip headers ('sndH') together with
+                    their associated sample data ('sndS') into a standalone
+                    playable .wav file. One file is created for each sndH/sndS
+                    pair, like --dump-all.
                     Supports 8-bit unsigned, and 16-, 24- and 32-bit signed PCM.
                     This tries to be generous with what it attempts to translate
                     and does not guarantee the resulting files are correct, but
                     it does output warnings when things don't look right.
+  --use-resource-map
+                    Not a generic RIFF option: specific to Macromedia Director.
+                    Instead of assuming chunks are laid out back-to-back from
+                    offset 12, locate the 'imap' chunk and follow it to the
+                    'mmap' resource map, then enumerate resources by walking
+                    that table. This surfaces the true resource indices
+                    (usable with --dump=INDEX), reports freed/overwritten
+                    entries, and copes with out-of-order chunks and gaps.
+  --mmap            Memory-map the input file instead of reading it normally,
+                    and serve chunk dumps/translations as slices of the
+                    mapping. Avoids copying large chunks into memory just to
+                    write them back out. Has no effect when reading from
+                    stdin.
+  --format=TYPE     Choose how the chunk listing is rendered: 'text' (the
+                    default) prints the indented tree described above, while
+                    'json' instead prints a single JSON document describing
+                    the file and its chunks (index, FourCC, sizes, offset,
+                    and nested children for container chunks), for feeding
+                    into other tools. Not currently supported together with
+                    --use-resource-map.
+  --lenient         Don't abort on the first truncated or malformed chunk
+                    while scanning. Instead, report the problem to stderr
+                    and try to resync to the next plausible chunk boundary
+                    so scanning can continue. A chunk whose size overruns
+                    the file or its container is not read at all (its size
+                    is not clamped); scanning resumes at the next plausible
+                    boundary after its start. Prints a summary of how many
+                    chunks were read and how many problems were recovered
+                    from at the end. Only affects the recursive chunk
+                    walker, not --use-resource-map.
 ",
         env!("CARGO_PKG_VERSION")
     );
@@ -65,7 +105,12 @@ fn main() -> Result<(), String> {
     let mut quiet_fourccs: HashSet<FourCC> = HashSet::new();
     let mut dump_fourccs: HashSet<FourCC> = HashSet::new();
     let mut dump_indices: HashSet<u32> = HashSet::new();
+    let mut container_fourccs: Option<HashSet<FourCC>> = None;
     let mut translate_sndh: bool = false;
+    let mut use_resource_map: bool = false;
+    let mut use_mmap: bool = false;
+    let mut format: OutputFormat = OutputFormat::Text;
+    let mut lenient: bool = false;
     for arg in &args[1..] {
         if arg == "--help" {
             return print_usage();
@@ -76,14 +121,26 @@ fn main() -> Result<(), String> {
         } else if let Some(index) = arg.strip_prefix("--dump=") {
             let index = u32::from_str(index).map_err(|e| e.to_string())?;
             dump_indices.insert(index);
+        } else if let Some(fourcc) = arg.strip_prefix("--container=") {
+            container_fourccs
+                .get_or_insert_with(HashSet::new)
+                .insert(convert_fourcc(fourcc)?);
         } else if arg == "--translate-sndH" {
             translate_sndh = true;
+        } else if arg == "--use-resource-map" {
+            use_resource_map = true;
+        } else if arg == "--mmap" {
+            use_mmap = true;
+        } else if let Some(value) = arg.strip_prefix("--format=") {
+            format = convert_format(value)?;
+        } else if arg == "--lenient" {
+            lenient = true;
         } else if arg.starts_with("--") {
             return Err(format!("Unknown argument: '{}'", arg));
         } else {
             match filename {
                 Some(_) => {
-                    return Err(format!("Only one filename can be specified."));
+                    return Err("Only one filename can be specified.".to_string());
                 }
                 None => {
                     filename = Some(arg);
@@ -92,18 +149,103 @@ fn main() -> Result<(), String> {
         }
     }
 
-    if let Some(filename) = filename {
-        let mut file = File::open(filename).map_err(convert_io_error)?;
+    let container_fourccs = container_fourccs.unwrap_or_else(|| {
+        let mut default = HashSet::new();
+        default.insert(LIST);
+        default.insert(RIFX);
+        default.insert(XFIR);
+        default
+    });
 
-        read_riff_file(
-            &mut file,
+    if let Some(filename) = filename {
+        read_riff_path(
+            filename,
             &quiet_fourccs,
             &dump_fourccs,
             &dump_indices,
+            &container_fourccs,
+            translate_sndh,
+            use_resource_map,
+            use_mmap,
+            format,
+            lenient,
+        )
+    } else {
+        Err("No filename was specified.".to_string())
+    }
+}
+
+/// Opens `filename` (or, if it's `-`, reads all of stdin into memory) and
+/// hands it off to [`read_riff_file`]. This is the thin, concrete entry
+/// point on top of the generic, stream-agnostic reading logic: it's where
+/// the choice between a plain `File`, a memory map, or a buffered stdin
+/// stream is made.
+#[allow(clippy::too_many_arguments)]
+fn read_riff_path(
+    filename: &str,
+    quiet_fourccs: &HashSet<FourCC>,
+    dump_fourccs: &HashSet<FourCC>,
+    dump_indices: &HashSet<u32>,
+    container_fourccs: &HashSet<FourCC>,
+    translate_sndh: bool,
+    use_resource_map: bool,
+    use_mmap: bool,
+    format: OutputFormat,
+    lenient: bool,
+) -> Result<(), String> {
+    if filename == "-" {
+        let mut buffer = Vec::new();
+        std::io::stdin()
+            .read_to_end(&mut buffer)
+            .map_err(convert_io_error)?;
+        let mut stream = Cursor::new(buffer);
+        return read_riff_file(
+            &mut stream,
+            quiet_fourccs,
+            dump_fourccs,
+            dump_indices,
+            container_fourccs,
             translate_sndh,
+            use_resource_map,
+            None,
+            format,
+            lenient,
+        );
+    }
+
+    let file = File::open(filename).map_err(convert_io_error)?;
+
+    if use_mmap {
+        // Safety: the file is not expected to be modified by another process
+        // while we're reading it; that's inherent to memory-mapping a file.
+        let mapping = unsafe { Mmap::map(&file) }.map_err(convert_io_error)?;
+        let mut stream = Cursor::new(&mapping[..]);
+        read_riff_file(
+            &mut stream,
+            quiet_fourccs,
+            dump_fourccs,
+            dump_indices,
+            container_fourccs,
+            translate_sndh,
+            use_resource_map,
+            Some(&mapping[..]),
+            format,
+            lenient,
         )
     } else {
-        Err(format!("No filename was specified."))
+        let mut file = file;
+        read_riff_file(
+            &mut file,
+            quiet_fourccs,
+            dump_fourccs,
+            dump_indices,
+            container_fourccs,
+            translate_sndh,
+            use_resource_map,
+            None,
+            format,
+            lenient,
+        )
     }
 }
 
@@ -117,10 +259,19 @@ fn format_fourcc(f: FourCC) -> String {
 
 const XFIR: FourCC = [b'X', b'F', b'I', b'R'];
 const RIFX: FourCC = [b'R', b'I', b'F', b'X'];
+const LIST: FourCC = [b'L', b'I', b'S', b'T'];
 #[allow(non_upper_case_globals)]
 const sndH: FourCC = [b's', b'n', b'd', b'H'];
+#[allow(non_upper_case_globals)]
+const sndS: FourCC = [b's', b'n', b'd', b'S'];
+#[allow(non_upper_case_globals)]
+const imap: FourCC = [b'i', b'm', b'a', b'p'];
+#[allow(non_upper_case_globals)]
+const mmap_fourcc: FourCC = [b'm', b'm', b'a', b'p'];
+#[allow(non_upper_case_globals)]
+const free: FourCC = [b'f', b'r', b'e', b'e'];
 
-fn read_fourcc(f: &mut File, byteswap: bool) -> Result<FourCC, String> {
+fn read_fourcc<R: Read>(f: &mut R, byteswap: bool) -> Result<FourCC, String> {
     let mut buffer = [0u8; 4];
     f.read_exact(&mut buffer).map_err(convert_io_error)?;
     if byteswap {
@@ -129,7 +280,7 @@ fn read_fourcc(f: &mut File, byteswap: bool) -> Result<FourCC, String> {
     Ok(buffer)
 }
 
-fn read_u32(f: &mut File, little_endian: bool) -> Result<u32, String> {
+fn read_u32<R: Read>(f: &mut R, little_endian: bool) -> Result<u32, String> {
     let mut buffer = [0u8; 4];
     f.read_exact(&mut buffer).map_err(convert_io_error)?;
     Ok(if little_endian {
@@ -139,53 +290,493 @@ fn read_u32(f: &mut File, little_endian: bool) -> Result<u32, String> {
     })
 }
 
-fn read_riff_file(
-    f: &mut File,
+fn read_u16<R: Read>(f: &mut R, little_endian: bool) -> Result<u16, String> {
+    let mut buffer = [0u8; 2];
+    f.read_exact(&mut buffer).map_err(convert_io_error)?;
+    Ok(if little_endian {
+        u16::from_le_bytes(buffer)
+    } else {
+        u16::from_be_bytes(buffer)
+    })
+}
+
+/// A chunk's payload bytes: either freshly read from a stream, or (when
+/// memory-mapping is enabled) borrowed directly from the mapping with no
+/// copy at all.
+enum Payload<'a> {
+    Owned(Vec<u8>),
+    Mapped(&'a [u8]),
+}
+
+impl<'a> Payload<'a> {
+    fn as_slice(&self) -> &[u8] {
+        match self {
+            Payload::Owned(v) => v,
+            Payload::Mapped(s) => s,
+        }
+    }
+}
+
+/// Reads `len` bytes starting at `start_offset`. If `raw` is present (the
+/// input is memory-mapped), the bytes are borrowed straight out of it
+/// instead of being copied through `f`; either way, `f`'s position ends up
+/// just past the payload, so callers don't need to care which path was
+/// taken.
+fn read_payload<'a, R: Read + Seek>(
+    f: &mut R,
+    raw: Option<&'a [u8]>,
+    start_offset: u32,
+    len: u32,
+) -> Result<Payload<'a>, String> {
+    if let Some(raw) = raw {
+        let start = start_offset as usize;
+        let end = start + len as usize;
+        if end > raw.len() {
+            return Err(format!(
+                "Tried to read {} bytes at offset {}, which is past the end \
+                 of the memory-mapped file ({} bytes)",
+                len,
+                start_offset,
+                raw.len()
+            ));
+        }
+        f.seek(SeekFrom::Start(end as u64))
+            .map_err(convert_io_error)?;
+        Ok(Payload::Mapped(&raw[start..end]))
+    } else {
+        f.seek(SeekFrom::Start(start_offset as u64))
+            .map_err(convert_io_error)?;
+        let mut buffer = vec![0u8; len as usize];
+        f.read_exact(&mut buffer).map_err(convert_io_error)?;
+        Ok(Payload::Owned(buffer))
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn read_riff_file<R: Read + Seek>(
+    f: &mut R,
     quiet_fourccs: &HashSet<FourCC>,
     dump_fourccs: &HashSet<FourCC>,
     dump_indices: &HashSet<u32>,
+    container_fourccs: &HashSet<FourCC>,
     translate_sndh: bool,
+    use_resource_map: bool,
+    raw: Option<&[u8]>,
+    format: OutputFormat,
+    lenient: bool,
 ) -> Result<(), String> {
+    if format == OutputFormat::Json && use_resource_map {
+        return Err(
+            "--format=json is not yet supported together with --use-resource-map".to_string(),
+        );
+    }
+
+    let text = format == OutputFormat::Text;
+
     let file_type = read_fourcc(f, false)?;
-    print!(
-        "File's magic number/FourCC is {}: ",
-        format_fourcc(file_type)
-    );
+    if text {
+        print!(
+            "File's magic number/FourCC is {}: ",
+            format_fourcc(file_type)
+        );
+    }
 
     let little_endian = if file_type == XFIR {
-        println!("Little-endian RIFX file.");
+        if text {
+            println!("Little-endian RIFX file.");
+        }
         true
     } else if file_type == RIFX {
-        println!("Big-endian RIFX file.");
+        if text {
+            println!("Big-endian RIFX file.");
+        }
         false
     } else {
-        return Err(format!("This format is not supported yet."));
+        return Err("This format is not supported yet.".to_string());
     };
 
     let file_size = read_u32(f, little_endian)?;
-    println!("File size according to RIFF header: {} bytes", file_size);
+    if text {
+        println!("File size according to RIFF header: {} bytes", file_size);
+    }
 
     let file_kind = read_fourcc(f, little_endian)?;
-    println!(
-        "File kind according to RIFF header: {}",
-        format_fourcc(file_kind)
-    );
+    if text {
+        println!(
+            "File kind according to RIFF header: {}",
+            format_fourcc(file_kind)
+        );
+    }
 
-    let mut offset: u32 = 12;
-    let mut index: u32 = 0;
-    while offset < file_size {
-        let chunk_type = read_fourcc(f, little_endian)?;
-        let chunk_size = read_u32(f, little_endian)?;
+    if use_resource_map {
+        read_resource_map(
+            f,
+            little_endian,
+            file_size,
+            quiet_fourccs,
+            dump_fourccs,
+            dump_indices,
+            translate_sndh,
+            raw,
+        )?;
+    } else {
+        let mut index: u32 = 0;
+        let mut pending_sndh: Option<SndHeader> = None;
+        let mut errors_recovered: u32 = 0;
+        let records = read_chunks(
+            f,
+            little_endian,
+            12,
+            file_size,
+            0,
+            &mut index,
+            quiet_fourccs,
+            dump_fourccs,
+            dump_indices,
+            container_fourccs,
+            translate_sndh,
+            &mut pending_sndh,
+            raw,
+            format,
+            lenient,
+            &mut errors_recovered,
+        )?;
+        if let Some(pending) = pending_sndh {
+            eprintln!(
+                "sndH #{} (offset {}) had no following sndS chunk; skipping",
+                pending.chunk_index, pending.chunk_offset
+            );
+        }
+
+        if lenient && text {
+            println!(
+                "Finished scanning: {} chunks read, {} errors recovered from",
+                index, errors_recovered
+            );
+        }
+
+        if format == OutputFormat::Json {
+            let chunks_json = records
+                .iter()
+                .map(chunk_record_to_json)
+                .collect::<Vec<_>>()
+                .join(",");
+            println!(
+                "{{\"little_endian\":{},\"header_size\":12,\"file_kind\":{},\"chunks\":[{}]}}",
+                little_endian,
+                fourcc_to_json(file_kind),
+                chunks_json
+            );
+        }
+    }
+
+    if text {
+        println!("Finished reading file without problems!");
+    }
+
+    Ok(())
+}
+
+/// A problem encountered while scanning chunks, classified so that
+/// `--lenient` mode can decide how to recover (or not) instead of every
+/// problem being treated the same way. Each variant carries the offset the
+/// problem was found at and a human-readable description.
+enum ChunkError {
+    /// A chunk header or payload was cut short by the end of the stream.
+    Truncated(u32, String),
+    /// A chunk's own data is self-contradictory, e.g. a declared size that
+    /// overruns the file or its containing chunk.
+    InvalidData(u32, String),
+    /// Something structurally present that this reader doesn't know how to
+    /// interpret safely.
+    Unsupported(u32, String),
+}
+
+impl ChunkError {
+    fn offset(&self) -> u32 {
+        match self {
+            ChunkError::Truncated(offset, _)
+            | ChunkError::InvalidData(offset, _)
+            | ChunkError::Unsupported(offset, _) => *offset,
+        }
+    }
+}
+
+impl std::fmt::Display for ChunkError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            ChunkError::Truncated(offset, message) => {
+                write!(f, "truncated data at offset {}: {}", offset, message)
+            }
+            ChunkError::InvalidData(offset, message) => {
+                write!(f, "invalid data at offset {}: {}", offset, message)
+            }
+            ChunkError::Unsupported(offset, message) => {
+                write!(f, "unsupported construct at offset {}: {}", offset, message)
+            }
+        }
+    }
+}
+
+impl From<ChunkError> for String {
+    fn from(e: ChunkError) -> String {
+        e.to_string()
+    }
+}
+
+/// Which rendering [`read_riff_file`] and [`read_chunks`] should produce:
+/// human-readable indented prose (the default), or a single machine-readable
+/// JSON document describing the file and its chunks.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum OutputFormat {
+    Text,
+    Json,
+}
+
+fn convert_format(arg: &str) -> Result<OutputFormat, String> {
+    match arg {
+        "text" => Ok(OutputFormat::Text),
+        "json" => Ok(OutputFormat::Json),
+        _ => Err(format!(
+            "'{}' is not a known --format (expected 'text' or 'json')",
+            arg
+        )),
+    }
+}
+
+/// One chunk's worth of information, as discovered by [`read_chunks`]:
+/// everything printed in text mode, and everything serialized in JSON mode.
+/// Container chunks carry their list kind and their nested chunks as
+/// `children`, recursively.
+struct ChunkRecord {
+    index: u32,
+    fourcc: FourCC,
+    declared_size: u32,
+    padded_size: u32,
+    offset: u32,
+    list_kind: Option<FourCC>,
+    children: Vec<ChunkRecord>,
+}
+
+fn json_escape(s: &str) -> String {
+    let mut out = String::with_capacity(s.len() + 2);
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+/// Renders a FourCC as a JSON object carrying both its ASCII form (or `null`
+/// if it isn't valid ASCII) and its raw bytes, so consumers can rely on
+/// whichever representation suits them.
+fn fourcc_to_json(fourcc: FourCC) -> String {
+    let string = if fourcc.is_ascii() {
+        json_escape(unsafe { std::str::from_utf8_unchecked(&fourcc) })
+    } else {
+        "null".to_string()
+    };
+    format!(
+        "{{\"string\":{},\"bytes\":[{},{},{},{}]}}",
+        string, fourcc[0], fourcc[1], fourcc[2], fourcc[3]
+    )
+}
+
+fn chunk_record_to_json(record: &ChunkRecord) -> String {
+    let list_kind = match record.list_kind {
+        Some(kind) => format!(",\"list_kind\":{}", fourcc_to_json(kind)),
+        None => String::new(),
+    };
+    let children = record
+        .children
+        .iter()
+        .map(chunk_record_to_json)
+        .collect::<Vec<_>>()
+        .join(",");
+    format!(
+        "{{\"index\":{},\"fourcc\":{},\"declared_size\":{},\"padded_size\":{},\"offset\":{}{},\"children\":[{}]}}",
+        record.index,
+        fourcc_to_json(record.fourcc),
+        record.declared_size,
+        record.padded_size,
+        record.offset,
+        list_kind,
+        children
+    )
+}
+
+/// Whether `bytes` could plausibly be a chunk FourCC: Director/RIFF FourCCs
+/// are always printable ASCII (letters, digits, spaces), even if they're not
+/// ones this reader recognizes. Used by [`resync`] to tell a real chunk
+/// boundary apart from arbitrary sample/compiled-code bytes.
+fn looks_like_fourcc(bytes: &FourCC) -> bool {
+    bytes
+        .iter()
+        .all(|&b| b == b' ' || b.is_ascii_graphic())
+}
+
+/// Reads a chunk's 8-byte header (type and size) at the current position,
+/// returning [`ChunkError::Truncated`] if the stream ends first and
+/// [`ChunkError::InvalidData`] if the declared size would overrun
+/// `end_offset`. On error, the stream is left wherever reading stopped, so
+/// callers must seek before reading again.
+fn read_chunk_header<R: Read + Seek>(
+    f: &mut R,
+    little_endian: bool,
+    offset: u32,
+    end_offset: u32,
+) -> Result<(FourCC, u32), ChunkError> {
+    let chunk_type = read_fourcc(f, little_endian)
+        .map_err(|e| ChunkError::Truncated(offset, e))?;
+    let chunk_size = read_u32(f, little_endian)
+        .map_err(|e| ChunkError::Truncated(offset, e))?;
+
+    if (offset + 8)
+        .checked_add(chunk_size)
+        .is_none_or(|payload_end| payload_end > end_offset)
+    {
+        return Err(ChunkError::InvalidData(
+            offset,
+            format!(
+                "chunk {} claims a size of {} bytes, which would extend past \
+                 the end of its containing chunk/file (offset {})",
+                format_fourcc(chunk_type),
+                chunk_size,
+                end_offset
+            ),
+        ));
+    }
+
+    Ok((chunk_type, chunk_size))
+}
+
+/// After a problem at `offset`, scans forward two bytes at a time (RIFF's
+/// own alignment) looking for a position that could plausibly be the start
+/// of the next chunk: a readable FourCC followed by a size that would fit in
+/// what's left of `end_offset`. Returns `None` if no such position is found
+/// before `end_offset`, meaning this level can't be recovered any further.
+fn resync<R: Read + Seek>(
+    f: &mut R,
+    little_endian: bool,
+    offset: u32,
+    end_offset: u32,
+) -> Result<Option<u32>, String> {
+    let mut candidate = offset + 2;
+    while candidate.checked_add(8).is_some_and(|h| h <= end_offset) {
+        f.seek(SeekFrom::Start(candidate as u64))
+            .map_err(convert_io_error)?;
+        // If the stream itself ends here, no later position can be read
+        // either.
+        let chunk_type = match read_fourcc(f, little_endian) {
+            Ok(chunk_type) => chunk_type,
+            Err(_) => return Ok(None),
+        };
+        let chunk_size = match read_u32(f, little_endian) {
+            Ok(chunk_size) => chunk_size,
+            Err(_) => return Ok(None),
+        };
+        let plausible = looks_like_fourcc(&chunk_type)
+            && (candidate + 8)
+                .checked_add(chunk_size)
+                .is_some_and(|payload_end| payload_end <= end_offset);
+        if plausible {
+            f.seek(SeekFrom::Start(candidate as u64))
+                .map_err(convert_io_error)?;
+            return Ok(Some(candidate));
+        }
+        candidate += 2;
+    }
+    Ok(None)
+}
+
+/// Reads the chunks found between `start_offset` and `end_offset`
+/// (exclusive), which might be the whole file or might be the payload of a
+/// single container chunk one level up, performing any requested dumping or
+/// translation along the way and returning a [`ChunkRecord`] per chunk (with
+/// nested records for container chunks' children). In `OutputFormat::Text`
+/// mode this also prints the chunk tree as it goes, indented by `depth`;
+/// `OutputFormat::Json` builds the records silently, to be serialized once
+/// reading finishes. `index` is a running, file-wide chunk counter that's
+/// threaded through so indices stay unique across recursion, the same way
+/// `--dump=INDEX` already expects them to be.
+#[allow(clippy::too_many_arguments)]
+fn read_chunks<R: Read + Seek>(
+    f: &mut R,
+    little_endian: bool,
+    start_offset: u32,
+    end_offset: u32,
+    depth: usize,
+    index: &mut u32,
+    quiet_fourccs: &HashSet<FourCC>,
+    dump_fourccs: &HashSet<FourCC>,
+    dump_indices: &HashSet<u32>,
+    container_fourccs: &HashSet<FourCC>,
+    translate_sndh: bool,
+    pending_sndh: &mut Option<SndHeader>,
+    raw: Option<&[u8]>,
+    format: OutputFormat,
+    lenient: bool,
+    errors_recovered: &mut u32,
+) -> Result<Vec<ChunkRecord>, String> {
+    let indent = "  ".repeat(depth);
+    let mut records = Vec::new();
+
+    let mut offset = start_offset;
+    while offset < end_offset {
         let chunk_offset = offset;
-        let chunk_index = index;
+
+        f.seek(SeekFrom::Start(chunk_offset as u64))
+            .map_err(convert_io_error)?;
+        let (chunk_type, chunk_size) =
+            match read_chunk_header(f, little_endian, chunk_offset, end_offset) {
+                Ok(header) => header,
+                Err(e) if lenient => {
+                    eprintln!("Recovering from problem while scanning: {}", e);
+                    *errors_recovered += 1;
+                    match resync(f, little_endian, e.offset(), end_offset)? {
+                        Some(resync_offset) => {
+                            offset = resync_offset;
+                            continue;
+                        }
+                        None => break,
+                    }
+                }
+                Err(e) => return Err(e.to_string()),
+            };
+        let chunk_index = *index;
         offset += 8;
 
         let quiet = quiet_fourccs.contains(&chunk_type);
         let dump = dump_fourccs.contains(&chunk_type) || dump_indices.contains(&chunk_index);
-        let translate = translate_sndh && chunk_type == sndH;
-        if !quiet {
+        let is_container = container_fourccs.contains(&chunk_type);
+        let text = format == OutputFormat::Text;
+
+        // An sndH only pairs with the sndS chunk immediately following it;
+        // anything else in between means there's nothing to translate.
+        if translate_sndh && chunk_type != sndS {
+            if let Some(pending) = pending_sndh.take() {
+                eprintln!(
+                    "sndH #{} (offset {}) had no following sndS chunk; skipping",
+                    pending.chunk_index, pending.chunk_offset
+                );
+            }
+        }
+        let translate = translate_sndh
+            && (chunk_type == sndH || (chunk_type == sndS && pending_sndh.is_some()));
+
+        if text && !quiet {
             println!(
-                "Chunk #{} of type {}, size {} bytes at offset {} bytes",
+                "{}Chunk #{} of type {}, size {} bytes at offset {} bytes",
+                indent,
                 chunk_index,
                 format_fourcc(chunk_type),
                 chunk_size,
@@ -196,17 +787,105 @@ fn read_riff_file(
         // RIFF pads chunk sizes to be 2-byte-aligned (the era of “DWORDs”…)
         let seek_size = chunk_size + (chunk_size & 1);
 
-        if !dump && !translate {
-            if !quiet {
-                println!("(skipping)");
+        *index += 1;
+
+        let mut list_kind: Option<FourCC> = None;
+        let mut children = Vec::new();
+
+        if is_container && chunk_size < 4 {
+            let e = ChunkError::Unsupported(
+                chunk_offset,
+                format!(
+                    "chunk {} is a container type, but its size of {} bytes \
+                     is too small to hold a list kind, so it can't be \
+                     interpreted as one",
+                    format_fourcc(chunk_type),
+                    chunk_size
+                ),
+            );
+            if lenient {
+                eprintln!("Recovering from problem while scanning: {}", e);
+                *errors_recovered += 1;
+                records.push(ChunkRecord {
+                    index: chunk_index,
+                    fourcc: chunk_type,
+                    declared_size: chunk_size,
+                    padded_size: seek_size,
+                    offset: chunk_offset,
+                    list_kind: None,
+                    children: Vec::new(),
+                });
+                match resync(f, little_endian, chunk_offset, end_offset)? {
+                    Some(resync_offset) => {
+                        offset = resync_offset;
+                        continue;
+                    }
+                    None => break,
+                }
+            } else {
+                return Err(e.to_string());
+            }
+        }
+
+        if is_container {
+            let kind = read_fourcc(f, little_endian)?;
+            list_kind = Some(kind);
+            if text && !quiet {
+                println!("{}  List kind: {}", indent, format_fourcc(kind));
+            }
+
+            children = read_chunks(
+                f,
+                little_endian,
+                chunk_offset + 12,
+                chunk_offset + 8 + chunk_size,
+                depth + 1,
+                index,
+                quiet_fourccs,
+                dump_fourccs,
+                dump_indices,
+                container_fourccs,
+                translate_sndh,
+                pending_sndh,
+                raw,
+                format,
+                lenient,
+                errors_recovered,
+            )?;
+
+            // The recursive call only consumes up to chunk_size bytes (it
+            // doesn't know about padding, since that's a property of this
+            // chunk, not its children), so skip the padding byte ourselves.
+            if seek_size != chunk_size {
+                f.seek(SeekFrom::Current(1)).map_err(convert_io_error)?;
+            }
+        } else if !dump && !translate {
+            if text && !quiet {
+                println!("{}(skipping)", indent);
             }
 
             f.seek(SeekFrom::Current(seek_size as i64))
                 .map_err(convert_io_error)?;
         } else {
-            let mut buffer = Vec::with_capacity(seek_size as usize);
-            buffer.resize(seek_size as usize, 0);
-            f.read_exact(&mut buffer[..]).map_err(convert_io_error)?;
+            let buffer = match read_payload(f, raw, chunk_offset + 8, seek_size) {
+                Ok(buffer) => buffer,
+                Err(message) if lenient => {
+                    let e = ChunkError::Truncated(chunk_offset, message);
+                    eprintln!("Recovering from problem while scanning: {}", e);
+                    *errors_recovered += 1;
+                    records.push(ChunkRecord {
+                        index: chunk_index,
+                        fourcc: chunk_type,
+                        declared_size: chunk_size,
+                        padded_size: seek_size,
+                        offset: chunk_offset,
+                        list_kind,
+                        children,
+                    });
+                    break;
+                }
+                Err(message) => return Err(message),
+            };
             if dump {
                 let filename = format!(
                     "{:04}-{}.{}",
@@ -214,24 +893,252 @@ fn read_riff_file(
                     chunk_offset,
                     std::str::from_utf8(&chunk_type).map_err(|e| e.to_string())?
                 );
+                if text && !quiet {
+                    print!("{}(dumping to: {}…", indent, filename);
+                }
+                std::fs::write(filename, buffer.as_slice()).map_err(convert_io_error)?;
+                if text && !quiet {
+                    println!(" done!)");
+                }
+            }
+            if translate {
+                if chunk_type == sndH {
+                    *pending_sndh = parse_sndh(buffer.as_slice(), chunk_index, chunk_offset)?;
+                } else if let Some(header) = pending_sndh.take() {
+                    write_sndh_wav(
+                        &header,
+                        &buffer.as_slice()[..chunk_size as usize],
+                        quiet || !text,
+                    )?;
+                }
+            }
+        }
+
+        records.push(ChunkRecord {
+            index: chunk_index,
+            fourcc: chunk_type,
+            declared_size: chunk_size,
+            padded_size: seek_size,
+            offset: chunk_offset,
+            list_kind,
+            children,
+        });
+
+        offset += seek_size;
+    }
+
+    Ok(records)
+}
+
+/// Scans the top-level chunks for an 'imap' chunk and returns the absolute
+/// file offset of the 'mmap' chunk it points to.
+fn find_imap<R: Read + Seek>(
+    f: &mut R,
+    little_endian: bool,
+    file_size: u32,
+) -> Result<u32, String> {
+    let mut offset: u32 = 12;
+    while offset < file_size {
+        let chunk_type = read_fourcc(f, little_endian)?;
+        let chunk_size = read_u32(f, little_endian)?;
+        let chunk_offset = offset;
+        offset += 8;
+
+        if chunk_type == imap {
+            if chunk_size < 8 {
+                return Err(format!(
+                    "'imap' chunk at offset {} is only {} bytes long, too \
+                     small to contain an 'mmap' offset",
+                    chunk_offset, chunk_size
+                ));
+            }
+            let _map_count = read_u32(f, little_endian)?;
+            let mmap_offset = read_u32(f, little_endian)?;
+            return Ok(mmap_offset);
+        }
+
+        let seek_size = chunk_size + (chunk_size & 1);
+        f.seek(SeekFrom::Current(seek_size as i64))
+            .map_err(convert_io_error)?;
+        offset += seek_size;
+    }
+    Err("No 'imap' chunk was found in this file".to_string())
+}
+
+/// Locates the 'imap' chunk, follows it to the 'mmap' chunk it points at,
+/// and enumerates the resource table found there instead of assuming chunks
+/// are laid out back-to-back from offset 12. This is how Director itself
+/// finds resources, and it correctly surfaces freed/overwritten entries,
+/// out-of-order chunks and gaps that a linear scan would miss or misread.
+/// `--dump=INDEX` addresses the true resource id in this mode.
+#[allow(clippy::too_many_arguments)]
+fn read_resource_map<R: Read + Seek>(
+    f: &mut R,
+    little_endian: bool,
+    file_size: u32,
+    quiet_fourccs: &HashSet<FourCC>,
+    dump_fourccs: &HashSet<FourCC>,
+    dump_indices: &HashSet<u32>,
+    translate_sndh: bool,
+    raw: Option<&[u8]>,
+) -> Result<(), String> {
+    let mmap_offset = find_imap(f, little_endian, file_size)?;
+    println!("'imap' points at 'mmap' chunk at offset {}", mmap_offset);
+
+    f.seek(SeekFrom::Start(mmap_offset as u64))
+        .map_err(convert_io_error)?;
+    let chunk_type = read_fourcc(f, little_endian)?;
+    if chunk_type != mmap_fourcc {
+        return Err(format!(
+            "'imap' points at offset {}, but the chunk there is of type {}, \
+             not 'mmap'",
+            mmap_offset,
+            format_fourcc(chunk_type)
+        ));
+    }
+    let _chunk_size = read_u32(f, little_endian)?;
+
+    let header_size = read_u16(f, little_endian)?;
+    let entry_size = read_u16(f, little_endian)?;
+    let entries_allocated = read_u32(f, little_endian)?;
+    let entries_used = read_u32(f, little_endian)?;
+    let free_list_head = read_u32(f, little_endian)?;
+    println!(
+        "'mmap' header: {} bytes, entries are {} bytes each, {} of {} \
+         allocated entries used, free list head is {}",
+        header_size, entry_size, entries_used, entries_allocated, free_list_head
+    );
+    if entry_size < 20 {
+        return Err(format!(
+            "'mmap' entries are only {} bytes long, too small to hold a \
+             resource entry",
+            entry_size
+        ));
+    }
+    // The fields read above make up a 16-byte header; skip any trailing
+    // fields a newer format version might have added that we don't know
+    // about.
+    if header_size > 16 {
+        f.seek(SeekFrom::Current((header_size - 16) as i64))
+            .map_err(convert_io_error)?;
+    }
+
+    // Reading a resource's payload below seeks `f` elsewhere, so entries
+    // can't just be read back-to-back; each one is located arithmetically
+    // from the start of the table instead.
+    let table_start = f.stream_position().map_err(convert_io_error)?;
+
+    let mut pending_sndh: Option<SndHeader> = None;
+    let mut resources_read = 0u32;
+    let mut resources_free = 0u32;
+    for index in 0..entries_used {
+        let entry_start = table_start + (index as u64) * (entry_size as u64);
+        f.seek(SeekFrom::Start(entry_start))
+            .map_err(convert_io_error)?;
+
+        let fourcc = read_fourcc(f, little_endian)?;
+        let size = read_u32(f, little_endian)?;
+        let offset = read_u32(f, little_endian)?;
+        let flags = read_u16(f, little_endian)?;
+        // The rest of the entry (at least an unused field and a free-list
+        // link) isn't needed to enumerate resources.
+
+        if fourcc == free {
+            resources_free += 1;
+            if !quiet_fourccs.contains(&fourcc) {
+                println!(
+                    "Resource #{} is free (type {}, size {} bytes, at \
+                     offset {} bytes)",
+                    index,
+                    format_fourcc(fourcc),
+                    size,
+                    offset
+                );
+            }
+            continue;
+        }
+
+        if offset
+            .checked_add(8)
+            .and_then(|header_end| header_end.checked_add(size))
+            .is_none_or(|resource_end| resource_end > file_size)
+        {
+            return Err(format!(
+                "Resource #{} of type {} claims offset {} and size {} \
+                 bytes, which extends past the end of the file",
+                index,
+                format_fourcc(fourcc),
+                offset,
+                size
+            ));
+        }
+
+        let quiet = quiet_fourccs.contains(&fourcc);
+        let dump = dump_fourccs.contains(&fourcc) || dump_indices.contains(&index);
+
+        if !quiet {
+            println!(
+                "Resource #{} of type {}, size {} bytes at offset {} bytes \
+                 (flags {:#06x})",
+                index,
+                format_fourcc(fourcc),
+                size,
+                offset,
+                flags
+            );
+        }
+
+        if translate_sndh && fourcc != sndS {
+            if let Some(pending) = pending_sndh.take() {
+                eprintln!(
+                    "sndH #{} (offset {}) had no following sndS chunk; skipping",
+                    pending.chunk_index, pending.chunk_offset
+                );
+            }
+        }
+        let translate =
+            translate_sndh && (fourcc == sndH || (fourcc == sndS && pending_sndh.is_some()));
+
+        if dump || translate {
+            let buffer = read_payload(f, raw, offset + 8, size)?;
+
+            if dump {
+                let filename = format!(
+                    "{:04}-{}.{}",
+                    index,
+                    offset,
+                    std::str::from_utf8(&fourcc).map_err(|e| e.to_string())?
+                );
                 if !quiet {
                     print!("(dumping to: {}…", filename);
                 }
-                std::fs::write(filename, &buffer[..]).map_err(convert_io_error)?;
+                std::fs::write(filename, buffer.as_slice()).map_err(convert_io_error)?;
                 if !quiet {
                     println!(" done!)");
                 }
             }
             if translate {
-                do_translate_sndh(&buffer, quiet, chunk_index, chunk_offset)?
+                if fourcc == sndH {
+                    pending_sndh = parse_sndh(buffer.as_slice(), index, offset)?;
+                } else if let Some(header) = pending_sndh.take() {
+                    write_sndh_wav(&header, buffer.as_slice(), quiet)?;
+                }
             }
         }
 
-        offset += seek_size;
-        index += 1;
+        resources_read += 1;
+    }
+    if let Some(pending) = pending_sndh {
+        eprintln!(
+            "sndH #{} (offset {}) had no following sndS chunk; skipping",
+            pending.chunk_index, pending.chunk_offset
+        );
     }
 
-    println!("Finished reading file without problems!");
+    println!(
+        "Finished walking resource map: {} resources read, {} free entries",
+        resources_read, resources_free
+    );
 
     Ok(())
 }
@@ -257,23 +1164,33 @@ macro_rules! tl_asserts {
     }
 }
 
-fn do_translate_sndh(
+/// The fields of a parsed `sndH` chunk that are needed to write out its
+/// associated `sndS` sample data as a standalone .wav file.
+struct SndHeader {
+    chunk_index: u32,
+    chunk_offset: u32,
+    channel_count: u32,
+    pcm_frames_per_second: u32,
+    bit_depth: u32,
+    bytes_per_frame: u32,
+}
+
+fn parse_sndh(
     buffer: &[u8],
-    quiet: bool,
     chunk_index: u32,
     chunk_offset: u32,
-) -> Result<(), String> {
+) -> Result<Option<SndHeader>, String> {
     if buffer.len() != 100 {
         eprintln!(
             "sndH #{} (offset {}) is not 100 bytes long; ignoring",
             chunk_index, chunk_offset,
         );
-        return Ok(());
+        return Ok(None);
     };
     let buffer = unsafe {
         let mut buffer = std::ptr::read_unaligned(buffer.as_ptr() as *const [u32; 25]);
-        for i in 0..buffer.len() {
-            buffer[i] = u32::from_be(buffer[i]) // Yes, not LE!
+        for word in &mut buffer {
+            *word = u32::from_be(*word) // Yes, not LE!
         }
         buffer
     };
@@ -336,38 +1253,160 @@ fn do_translate_sndh(
         bytes_per_frame == bytes_per_sample * channel_count
     );
 
-    // Note the inconsistent endianness! I don't know why this is.
-    // Note also that the bit depth alone seems to determine the format.
-    // Director 8.5 doesn't seem to import  µ-law, A-law or float WAV files,
-    // so these are the only formats I know about.
-    // Importing an IMA ADPCM file resulted in an 'ediM' chunk instead.
-    let format = match bit_depth {
-        8 => "u8",
-        16 => "s16be", // Observed in the wild and contrived Director 8.5 file
-        24 => "s24le", // Seen only in contrived Director 8.5 testing file
-        32 => "s32le", // ditto
-        _ => {
-            eprintln!(
-                "sndH #{} (offset {}) has unexpected bit-depth {}; ignoring",
-                chunk_index, chunk_offset, bit_depth
-            );
-            return Ok(());
+    // Note also that the bit depth alone seems to determine the sample format:
+    // 8-bit unsigned, and 16- (observed in the wild), 24- and 32-bit (seen
+    // only in contrived Director 8.5 test files) signed PCM. Director 8.5
+    // doesn't seem to import µ-law, A-law or float WAV files, so these are
+    // the only formats known about. Importing an IMA ADPCM file resulted in
+    // an 'ediM' chunk instead.
+    if !matches!(bit_depth, 8 | 16 | 24 | 32) {
+        eprintln!(
+            "sndH #{} (offset {}) has unexpected bit-depth {}; ignoring",
+            chunk_index, chunk_offset, bit_depth
+        );
+        return Ok(None);
+    }
+
+    Ok(Some(SndHeader {
+        chunk_index,
+        chunk_offset,
+        channel_count,
+        pcm_frames_per_second,
+        bit_depth,
+        bytes_per_frame,
+    }))
+}
+
+/// Director stores 16-bit PCM big-endian ("s16be"), but WAV requires
+/// little-endian samples, so each sample pair needs byte-swapping. The
+/// 8-bit unsigned and 24-/32-bit little-endian cases already match what
+/// WAV expects and pass through unchanged.
+fn sndh_samples_to_wav_endianness(pcm_data: &[u8], bit_depth: u32) -> Vec<u8> {
+    let mut samples = pcm_data.to_vec();
+    if bit_depth == 16 {
+        for pair in samples.chunks_exact_mut(2) {
+            pair.swap(0, 1);
         }
-    };
-    let ffmpeg_args = format!(
-        "-f {} -ac {} -ar {}",
-        format, channel_count, pcm_frames_per_second,
-    );
+    }
+    samples
+}
+
+/// Writes `pcm_data` (the payload of the `sndS` chunk associated with
+/// `header`) out as a standalone RIFF/WAVE file, converting it from
+/// Director's on-disk sample format to the one WAV expects along the way.
+fn write_sndh_wav(header: &SndHeader, pcm_data: &[u8], quiet: bool) -> Result<(), String> {
+    let samples = sndh_samples_to_wav_endianness(pcm_data, header.bit_depth);
 
-    let filename = format!("{:04}-{}-sndH.txt", chunk_index, chunk_offset,);
+    let audio_format: u16 = 1; // PCM
+    let byte_rate = header.pcm_frames_per_second * header.bytes_per_frame;
+    let data_size = samples.len() as u32;
+    let fmt_size: u32 = 16;
+    // "WAVE" + ("fmt " + size + contents) + ("data" + size + contents)
+    let riff_size = 4 + (8 + fmt_size) + (8 + data_size);
+
+    let mut wav = Vec::with_capacity(8 + riff_size as usize);
+    wav.extend_from_slice(b"RIFF");
+    wav.extend_from_slice(&riff_size.to_le_bytes());
+    wav.extend_from_slice(b"WAVE");
+    wav.extend_from_slice(b"fmt ");
+    wav.extend_from_slice(&fmt_size.to_le_bytes());
+    wav.extend_from_slice(&audio_format.to_le_bytes());
+    wav.extend_from_slice(&(header.channel_count as u16).to_le_bytes());
+    wav.extend_from_slice(&header.pcm_frames_per_second.to_le_bytes());
+    wav.extend_from_slice(&byte_rate.to_le_bytes());
+    wav.extend_from_slice(&(header.bytes_per_frame as u16).to_le_bytes());
+    wav.extend_from_slice(&(header.bit_depth as u16).to_le_bytes());
+    wav.extend_from_slice(b"data");
+    wav.extend_from_slice(&data_size.to_le_bytes());
+    wav.extend_from_slice(&samples);
+
+    let filename = format!(
+        "{:04}-{}-sndH.wav",
+        header.chunk_index, header.chunk_offset,
+    );
 
     if !quiet {
         print!("(writing translated sndH to: {}…", filename);
     }
-    std::fs::write(filename, ffmpeg_args.as_bytes()).map_err(convert_io_error)?;
+    std::fs::write(filename, &wav).map_err(convert_io_error)?;
     if !quiet {
         println!(" done!)");
     }
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Builds a minimal synthetic RIFX-style buffer (big-endian) with one
+    // container chunk ('LIST') holding one leaf chunk ('DATA'), to exercise
+    // read_chunks' recursive descent without needing a real file on disk.
+    fn sample_container_buffer() -> Vec<u8> {
+        let mut buffer = Vec::new();
+        buffer.extend_from_slice(b"LIST"); // chunk type
+        buffer.extend_from_slice(&16u32.to_be_bytes()); // chunk size (list kind + child)
+        buffer.extend_from_slice(b"CST "); // list kind
+        buffer.extend_from_slice(b"DATA"); // child chunk type
+        buffer.extend_from_slice(&4u32.to_be_bytes()); // child chunk size
+        buffer.extend_from_slice(b"abcd"); // child payload
+        buffer
+    }
+
+    #[test]
+    fn read_chunks_walks_nested_container() {
+        let buffer = sample_container_buffer();
+        let end_offset = buffer.len() as u32;
+        let mut cursor = Cursor::new(buffer);
+
+        let mut index = 0;
+        let mut pending_sndh = None;
+        let mut errors_recovered = 0;
+        let container_fourccs: HashSet<FourCC> = [LIST].into_iter().collect();
+
+        let records = read_chunks(
+            &mut cursor,
+            false,
+            0,
+            end_offset,
+            0,
+            &mut index,
+            &HashSet::new(),
+            &HashSet::new(),
+            &HashSet::new(),
+            &container_fourccs,
+            false,
+            &mut pending_sndh,
+            None,
+            OutputFormat::Json,
+            false,
+            &mut errors_recovered,
+        )
+        .unwrap();
+
+        assert_eq!(records.len(), 1);
+        let list = &records[0];
+        assert_eq!(list.fourcc, LIST);
+        assert_eq!(list.list_kind, Some(*b"CST "));
+        assert_eq!(list.children.len(), 1);
+        assert_eq!(list.children[0].fourcc, *b"DATA");
+        assert_eq!(list.children[0].declared_size, 4);
+    }
+
+    #[test]
+    fn sndh_samples_to_wav_endianness_swaps_16_bit_samples() {
+        // Two big-endian 16-bit Director samples; WAV wants them little-endian.
+        let pcm_data = [0x01, 0x02, 0x03, 0x04];
+        let samples = sndh_samples_to_wav_endianness(&pcm_data, 16);
+        assert_eq!(samples, [0x02, 0x01, 0x04, 0x03]);
+    }
+
+    #[test]
+    fn sndh_samples_to_wav_endianness_passes_through_8_bit_samples() {
+        // 8-bit unsigned samples already match WAV's expectations unchanged.
+        let pcm_data = [0x01, 0x02, 0x03, 0x04];
+        let samples = sndh_samples_to_wav_endianness(&pcm_data, 8);
+        assert_eq!(samples, pcm_data);
+    }
+}